@@ -0,0 +1,137 @@
+//! Throughput/latency benchmark for a test bench, driving it as fast as
+//! possible to measure the cost of event-source injection and sink
+//! draining rather than timing anything over the network.
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use nexosim::simulation::Simulation;
+
+use grpc_python::sims;
+
+/// Drive a bench as fast as possible and report throughput/latency.
+#[derive(Parser)]
+#[command(about)]
+struct Cli {
+    /// The bench to profile.
+    bench: Bench,
+
+    /// Number of events to push through the bench's event source. Must be
+    /// at least 1, since percentiles need at least one sample.
+    #[arg(short, long, default_value_t = 100_000, value_parser = clap::value_parser!(usize).range(1..))]
+    events: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Bench {
+    Coffee,
+    Bench2,
+}
+
+impl std::str::FromStr for Bench {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "coffee" => Ok(Self::Coffee),
+            "bench2" => Ok(Self::Bench2),
+            _ => Err(format!("{s} bench not recognized.")),
+        }
+    }
+}
+
+/// Percentiles and throughput for a series of per-step timings.
+struct Stats {
+    events_per_sec: f64,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+}
+
+fn stats(mut samples: Vec<Duration>, total_elapsed: Duration) -> Stats {
+    samples.sort_unstable();
+
+    let percentile = |p: f64| -> Duration {
+        let idx = ((samples.len() - 1) as f64 * p) as usize;
+        samples[idx]
+    };
+
+    Stats {
+        events_per_sec: samples.len() as f64 / total_elapsed.as_secs_f64(),
+        p50: percentile(0.50),
+        p90: percentile(0.90),
+        p99: percentile(0.99),
+    }
+}
+
+fn print_stats(label: &str, stats: &Stats) {
+    println!("{label}:");
+    println!("  throughput: {:.0} events/s", stats.events_per_sec);
+    println!("  p50 latency: {:?}", stats.p50);
+    println!("  p90 latency: {:?}", stats.p90);
+    println!("  p99 latency: {:?}", stats.p99);
+}
+
+/// Call `push` on `sim` `events` times (each call injects and processes one
+/// event), and return the latency of each call together with the total
+/// elapsed time.
+fn profile_source(
+    sim: &mut Simulation,
+    mut push: impl FnMut(&mut Simulation) -> Result<(), nexosim::simulation::ExecutionError>,
+    events: usize,
+) -> (Vec<Duration>, Duration) {
+    let mut latencies = Vec::with_capacity(events);
+    let run_start = Instant::now();
+
+    for _ in 0..events {
+        let step_start = Instant::now();
+        push(sim).unwrap();
+        latencies.push(step_start.elapsed());
+    }
+
+    (latencies, run_start.elapsed())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match cli.bench {
+        Bench::Coffee => {
+            let (mut sim, mut registry) = sims::coffee_bench(None).unwrap();
+            let mut tank_fill = registry.get_event_source("tank_fill").unwrap();
+
+            let (latencies, elapsed) = profile_source(
+                &mut sim,
+                |sim| sim.process_event(&mut tank_fill, 1.0e-3),
+                cli.events,
+            );
+            print_stats("tank_fill event source", &stats(latencies, elapsed));
+
+            let flow_rate = registry.get_event_sink("flow_rate").unwrap();
+            let drain_start = Instant::now();
+            let drained = flow_rate.drain().count();
+            println!(
+                "flow_rate sink: drained {drained} events in {:?}",
+                drain_start.elapsed()
+            );
+        }
+        Bench::Bench2 => {
+            let (mut sim, mut registry) = sims::bench_2(Default::default()).unwrap();
+            let mut input = registry.get_event_source("input").unwrap();
+
+            let (latencies, elapsed) = profile_source(
+                &mut sim,
+                |sim| sim.process_event(&mut input, Default::default()),
+                cli.events,
+            );
+            print_stats("input event source", &stats(latencies, elapsed));
+
+            let output = registry.get_event_sink("output").unwrap();
+            let drain_start = Instant::now();
+            let drained = output.drain().count();
+            println!(
+                "output sink: drained {drained} events in {:?}",
+                drain_start.elapsed()
+            );
+        }
+    }
+}
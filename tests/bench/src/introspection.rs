@@ -0,0 +1,57 @@
+//! Thin wrapper over [`EndpointRegistry`] that turns a bench's registered
+//! endpoints into a queryable schema, plus a snapshot of a running
+//! [`Simulation`]'s current time. Used by the CLI's `--describe` flag;
+//! nothing in the server exposes this over gRPC.
+
+use nexosim::registry::EndpointRegistry;
+use nexosim::simulation::Simulation;
+use nexosim::time::MonotonicTime;
+
+/// Whether a registered endpoint is an event source or an event sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointKind {
+    Source,
+    Sink,
+}
+
+/// One registered endpoint and the Rust type of the events it carries.
+#[derive(Debug, Clone)]
+pub struct EndpointInfo {
+    pub name: String,
+    pub kind: EndpointKind,
+    pub value_type: &'static str,
+}
+
+/// List every endpoint registered on `registry`, source and sink alike.
+pub fn describe(registry: &EndpointRegistry) -> Vec<EndpointInfo> {
+    let sources = registry
+        .event_source_names()
+        .map(|name| EndpointInfo {
+            value_type: registry.event_source_type_name(&name),
+            name,
+            kind: EndpointKind::Source,
+        });
+
+    let sinks = registry
+        .event_sink_names()
+        .map(|name| EndpointInfo {
+            value_type: registry.event_sink_type_name(&name),
+            name,
+            kind: EndpointKind::Sink,
+        });
+
+    sources.chain(sinks).collect()
+}
+
+/// Snapshot of a running simulation's current time.
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    pub current_time: MonotonicTime,
+}
+
+/// Report `sim`'s current simulation time.
+pub fn health(sim: &Simulation) -> Health {
+    Health {
+        current_time: sim.time(),
+    }
+}
@@ -1,22 +1,93 @@
 //! Tool for starting a nexosim server set up with a test bench.
 use nexosim::server;
+use nexosim::registry::EndpointRegistry;
+use nexosim::simulation::{Simulation, SimulationError};
 use clap::Parser;
 use grpc_python::sims;
+use grpc_python::manifest;
+use grpc_python::mqtt;
+use grpc_python::vsock;
+use grpc_python::server_ext;
+use grpc_python::introspection;
 
 /// Start a nexosim server set up with a test bench.
 #[derive(Parser)]
 #[command(about)]
 struct Cli {
-    /// The bench the server will be set up with.
-    bench: Bench,
+    /// The bench the server will be set up with. Required unless
+    /// `--manifest` is given.
+    bench: Option<Bench>,
+
+    /// Load the bench assembly from a TOML manifest file instead of one of
+    /// the built-in `Bench` variants.
+    #[arg(long, conflicts_with = "bench")]
+    manifest: Option<String>,
+
+    /// Print the bench's registered endpoints and current simulation time,
+    /// then exit instead of starting a server.
+    #[arg(long)]
+    describe: bool,
 
     /// Start a http server instead of the default local unix server.
     #[arg(long)]
     http: bool,
 
-    /// Set the address of the server.
+    /// Start a VM-socket (AF_VSOCK) server instead of the default local unix
+    /// server. Conflicts with `--http`.
+    #[arg(long, conflicts_with = "http")]
+    vsock: bool,
+
+    /// Set the address of the server. For `--vsock`, this takes the form
+    /// `CID:port` (e.g. `3:41633`, or `-1:41633` to listen on any CID).
     #[arg(short, long)]
-    address: Option<String>
+    address: Option<String>,
+
+    /// Bridge the bench's registered event sources/sinks to an MQTT broker
+    /// (e.g. `mqtt://localhost:1883`), alongside the gRPC server.
+    #[arg(long)]
+    mqtt: Option<String>,
+
+    /// Number of executor threads to run the simulation with. Defaults to
+    /// `SimInit`'s own default.
+    #[arg(long, value_parser = clap::value_parser!(usize).range(1..))]
+    threads: Option<usize>,
+
+    /// Advance simulated time at this multiple of wall-clock time (e.g. `10`
+    /// for 10x faster, `0.5` for slow-motion). Applies to `CoffeeRT`, `Bench2`
+    /// and manifests with `clock = "wall_clock"`; `Coffee` and `fastest`
+    /// manifests ignore it. Without it, `CoffeeRT` and `wall_clock`
+    /// manifests run at `1` (real time) and `Bench2` runs as fast as possible.
+    #[arg(long, value_parser = positive_f64)]
+    clock_scale: Option<f64>,
+}
+
+/// Parse a `--clock-scale` value, rejecting zero/negative scales (which
+/// would leave `AutoSystemClock` unable to ever advance).
+fn positive_f64(s: &str) -> Result<f64, String> {
+    let scale: f64 = s.parse().map_err(|_| format!("{s} is not a valid number"))?;
+    if scale > 0.0 {
+        Ok(scale)
+    } else {
+        Err(format!("clock scale must be greater than 0, got {scale}"))
+    }
+}
+
+/// Parse a VM-socket address of the form `CID:port`. `-1` is accepted as
+/// the CID, meaning "any" (`VMADDR_CID_ANY`), matching `man vsock`'s own
+/// `-1`/`0xFFFFFFFF` convention.
+fn parse_vsock_addr(addr: &str) -> Result<(u32, u32), String> {
+    let (cid, port) = addr
+        .split_once(':')
+        .ok_or_else(|| format!("{addr} is not a valid CID:port vsock address"))?;
+
+    let cid: u32 = if cid == "-1" {
+        tokio_vsock::VMADDR_CID_ANY
+    } else {
+        cid.parse().map_err(|_| format!("{cid} is not a valid vsock CID"))?
+    };
+    let port: u32 = port.parse().map_err(|_| format!("{port} is not a valid vsock port"))?;
+
+    Ok((cid, port))
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -26,6 +97,18 @@ enum Bench {
     Bench2,
 }
 
+impl Bench {
+    /// Name used as the bench component of an MQTT topic, e.g.
+    /// `nexosim/coffee/flow_rate`.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Coffee => "coffee",
+            Self::CoffeeRT => "coffeert",
+            Self::Bench2 => "bench2",
+        }
+    }
+}
+
 impl std::str::FromStr for Bench {
     type Err = String;
 
@@ -39,51 +122,171 @@ impl std::str::FromStr for Bench {
     }
 }
 
+/// Build `bench` (or the manifest bench, set by [`manifest::set_manifest_path`]
+/// if `bench` is `None`) directly, without starting a server. Used by
+/// `--describe`.
+fn build_bench(bench: Option<Bench>) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    match bench {
+        None => manifest::manifest_bench(()),
+        Some(Bench::Coffee) => sims::coffee_bench(None),
+        Some(Bench::CoffeeRT) => sims::rt_coffee_bench(None),
+        Some(Bench::Bench2) => sims::bench_2(Default::default()),
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
+    if cli.bench.is_none() && cli.manifest.is_none() {
+        eprintln!("either a bench or --manifest <path> must be given");
+        std::process::exit(1);
+    }
+
     let addr = match cli.address {
         None => {
             if cli.http {
                 String::from("0.0.0.0:41633")
+            } else if cli.vsock {
+                String::from("-1:41633")
             } else {
                 String::from("/tmp/nexo")
             }
-        } 
+        }
         Some(value) => {
             value
         }
     };
 
-    if cli.http{
-        match cli.bench {
-            Bench::Coffee => {
+    if let Some(manifest_path) = cli.manifest {
+        manifest::set_manifest_path(manifest_path);
+    }
+
+    sims::set_runtime_config(sims::RuntimeConfig {
+        num_threads: cli.threads,
+        clock: sims::clock_mode_for_scale(cli.clock_scale),
+    });
+
+    let bench = cli.bench;
+    let bench_name = bench.map_or("manifest", Bench::name);
+    let broker_url = cli.mqtt;
+
+    if cli.describe {
+        let (sim, registry) = build_bench(bench).unwrap();
+
+        for endpoint in introspection::describe(&registry) {
+            println!("{:?} {}: {}", endpoint.kind, endpoint.name, endpoint.value_type);
+        }
+        println!("current_time: {:?}", introspection::health(&sim).current_time);
+
+        return;
+    }
+
+    if cli.vsock {
+        let (cid, port) = parse_vsock_addr(&addr).unwrap();
+
+        match (bench, &broker_url) {
+            (None, None) => {
+                println!("VSOCK manifest server listening at {}", addr);
+                vsock::run_vsock(manifest::manifest_bench, cid, port)
+            },
+            (None, Some(broker)) => {
+                println!("VSOCK manifest server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_vsock_with_mqtt(manifest::manifest_bench, cid, port, bench_name, broker)
+            },
+            (Some(Bench::Coffee), None) => {
+                println!("VSOCK Coffee server listening at {}", addr);
+                vsock::run_vsock(sims::coffee_bench, cid, port)
+            },
+            (Some(Bench::Coffee), Some(broker)) => {
+                println!("VSOCK Coffee server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_vsock_with_mqtt(sims::coffee_bench, cid, port, bench_name, broker)
+            },
+            (Some(Bench::CoffeeRT), None) => {
+                println!("VSOCK CoffeeRT server listening at {}", addr);
+                vsock::run_vsock(sims::rt_coffee_bench, cid, port)
+            },
+            (Some(Bench::CoffeeRT), Some(broker)) => {
+                println!("VSOCK CoffeeRT server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_vsock_with_mqtt(sims::rt_coffee_bench, cid, port, bench_name, broker)
+            },
+            (Some(Bench::Bench2), None) => {
+                println!("VSOCK Bench2 server listening at {}", addr);
+                vsock::run_vsock(sims::bench_2, cid, port)
+            },
+            (Some(Bench::Bench2), Some(broker)) => {
+                println!("VSOCK Bench2 server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_vsock_with_mqtt(sims::bench_2, cid, port, bench_name, broker)
+            },
+        }.unwrap();
+    } else if cli.http{
+        match (bench, &broker_url) {
+            (None, None) => {
+                println!("HTTP manifest server listening at {}", addr);
+                server::run(manifest::manifest_bench, addr.parse().unwrap()).map_err(server_ext::external)
+            },
+            (None, Some(broker)) => {
+                println!("HTTP manifest server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_with_mqtt(manifest::manifest_bench, addr.parse().unwrap(), bench_name, broker)
+            },
+            (Some(Bench::Coffee), None) => {
                 println!("HTTP Coffee server listening at {}", addr);
-                server::run(sims::coffee_bench, addr.parse().unwrap())
+                server::run(sims::coffee_bench, addr.parse().unwrap()).map_err(server_ext::external)
+            },
+            (Some(Bench::Coffee), Some(broker)) => {
+                println!("HTTP Coffee server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_with_mqtt(sims::coffee_bench, addr.parse().unwrap(), bench_name, broker)
             },
-            Bench::CoffeeRT => {
+            (Some(Bench::CoffeeRT), None) => {
                 println!("HTTP CoffeeRT server listening at {}", addr);
-                server::run(sims::rt_coffee_bench, addr.parse().unwrap())
+                server::run(sims::rt_coffee_bench, addr.parse().unwrap()).map_err(server_ext::external)
             },
-            Bench::Bench2 => {
+            (Some(Bench::CoffeeRT), Some(broker)) => {
+                println!("HTTP CoffeeRT server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_with_mqtt(sims::rt_coffee_bench, addr.parse().unwrap(), bench_name, broker)
+            },
+            (Some(Bench::Bench2), None) => {
                 println!("HTTP Bench2 server listening at {}", addr);
-                server::run(sims::bench_2, addr.parse().unwrap())
-            }
+                server::run(sims::bench_2, addr.parse().unwrap()).map_err(server_ext::external)
+            },
+            (Some(Bench::Bench2), Some(broker)) => {
+                println!("HTTP Bench2 server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_with_mqtt(sims::bench_2, addr.parse().unwrap(), bench_name, broker)
+            },
         }.unwrap();
     } else {
-        match cli.bench {
-            Bench::Coffee => {
+        match (bench, &broker_url) {
+            (None, None) => {
+                println!("Local manifest server listening at {}", addr);
+                server::run_local(manifest::manifest_bench, addr).map_err(server_ext::external)
+            },
+            (None, Some(broker)) => {
+                println!("Local manifest server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_local_with_mqtt(manifest::manifest_bench, addr, bench_name, broker)
+            },
+            (Some(Bench::Coffee), None) => {
                 println!("Local Coffee server listening at {}", addr);
-                server::run_local(sims::coffee_bench, addr)
+                server::run_local(sims::coffee_bench, addr).map_err(server_ext::external)
             },
-            Bench::CoffeeRT => {
+            (Some(Bench::Coffee), Some(broker)) => {
+                println!("Local Coffee server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_local_with_mqtt(sims::coffee_bench, addr, bench_name, broker)
+            },
+            (Some(Bench::CoffeeRT), None) => {
                 println!("Local CoffeeRT server listening at {}", addr);
-                server::run_local(sims::rt_coffee_bench, addr)
+                server::run_local(sims::rt_coffee_bench, addr).map_err(server_ext::external)
+            },
+            (Some(Bench::CoffeeRT), Some(broker)) => {
+                println!("Local CoffeeRT server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_local_with_mqtt(sims::rt_coffee_bench, addr, bench_name, broker)
             },
-            Bench::Bench2 => {
+            (Some(Bench::Bench2), None) => {
                 println!("Local Bench2 server listening at {}", addr);
-                server::run_local(sims::bench_2, addr)
-            }
+                server::run_local(sims::bench_2, addr).map_err(server_ext::external)
+            },
+            (Some(Bench::Bench2), Some(broker)) => {
+                println!("Local Bench2 server listening at {}, bridged to {}", addr, broker);
+                mqtt::run_local_with_mqtt(sims::bench_2, addr, bench_name, broker)
+            },
         }.unwrap();
     }
 }
\ No newline at end of file
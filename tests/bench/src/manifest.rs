@@ -0,0 +1,428 @@
+//! Declarative bench assembly: build a bench from a TOML manifest instead
+//! of a hand-written `sims::coffee_bench`/`bench_2`-style function.
+//!
+//! Ports are statically typed, so a manifest can't rewire models into an
+//! arbitrary topology; it recognizes one of the fixed built-in topologies
+//! from its declared model types, validates `connections` against that
+//! topology's real wiring, and registers endpoints under the manifest's
+//! own names.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use nexosim::ports::{EventSlot, EventBuffer, EventSource};
+use nexosim::registry::EndpointRegistry;
+use nexosim::simulation::{Mailbox, Simulation, SimulationError};
+use nexosim::time::MonotonicTime;
+
+use serde::Deserialize;
+
+use crate::coffee;
+use crate::bench_2;
+use crate::sims;
+
+/// Top-level description of a bench assembly.
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    /// Model instances to create, keyed by the name used in `connections`
+    /// and `endpoints`.
+    pub models: HashMap<String, ModelSpec>,
+
+    /// Port-to-port connections between model instances. Must match the
+    /// real wiring of whichever built-in topology `models` resolves to;
+    /// see [`build`].
+    #[serde(default)]
+    pub connections: Vec<ConnectionSpec>,
+
+    /// Ports exposed as named event sources/sinks.
+    #[serde(default)]
+    pub endpoints: Vec<EndpointSpec>,
+
+    /// Clock to assemble the simulation with. Defaults to the fastest
+    /// (non-real-time) clock.
+    #[serde(default)]
+    pub clock: ClockSpec,
+}
+
+/// A single model instance: its registered type name (`coffee::Pump`,
+/// `coffee::Controller`, `coffee::Tank`, `bench_2::MyModel`) and
+/// constructor parameters.
+#[derive(Debug, Deserialize)]
+pub struct ModelSpec {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    #[serde(default)]
+    pub params: Option<toml::Value>,
+}
+
+impl ModelSpec {
+    /// The numeric value of `params.<key>`, or `None` if `params` or `key`
+    /// is absent. Panics if `key` is present but isn't a number, rather
+    /// than silently falling back to the caller's default.
+    fn param_f64(&self, key: &str) -> Option<f64> {
+        let value = self.params.as_ref()?.get(key)?;
+        Some(
+            value
+                .as_float()
+                .or_else(|| value.as_integer().map(|i| i as f64))
+                .unwrap_or_else(|| panic!("manifest param `{key}` = {value} is not a number")),
+        )
+    }
+}
+
+/// A connection between an output port on one model and an input port on
+/// another.
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone)]
+pub struct ConnectionSpec {
+    pub from_model: String,
+    pub from_port: String,
+    pub to_model: String,
+    pub to_port: String,
+}
+
+/// A model port registered as a named `EventSource`/`EventSink`/
+/// `EventBuffer` endpoint.
+#[derive(Debug, Deserialize)]
+pub struct EndpointSpec {
+    pub model: String,
+    pub port: String,
+    pub name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSpec {
+    #[default]
+    Fastest,
+    WallClock,
+}
+
+/// Look up the one model in `manifest.models` whose declared type is
+/// `type_name`, by the name the manifest gave it.
+fn find_model<'a>(manifest: &'a Manifest, type_name: &str) -> (&'a str, &'a ModelSpec) {
+    let (name, spec) = manifest
+        .models
+        .iter()
+        .find(|(_, spec)| spec.type_name == type_name)
+        .unwrap_or_else(|| panic!("manifest has no model of type `{type_name}`"));
+    (name.as_str(), spec)
+}
+
+/// Check that `manifest.connections` is exactly the fixed set of wires a
+/// topology uses (as `(from_model, from_port, to_model, to_port)`
+/// references by manifest-given model name), so a manifest can't silently
+/// describe a graph that is never actually built.
+fn check_connections(manifest: &Manifest, expected: &[ConnectionSpec]) {
+    let declared: HashSet<_> = manifest.connections.iter().cloned().collect();
+    let expected: HashSet<_> = expected.iter().cloned().collect();
+
+    if declared != expected {
+        panic!(
+            "manifest `connections` {declared:?} do not match this topology's fixed wiring \
+             {expected:?}; this subsystem cannot rewire compiled model ports, only validate \
+             and register them"
+        );
+    }
+}
+
+/// Find the endpoint the manifest registers for `(model_name, port)`, if
+/// any.
+fn endpoint_name<'a>(manifest: &'a Manifest, model_name: &str, port: &str) -> Option<&'a str> {
+    manifest
+        .endpoints
+        .iter()
+        .find(|e| e.model == model_name && e.port == port)
+        .map(|e| e.name.as_str())
+}
+
+/// Assemble the `coffee` topology (`Pump`, `Controller`, `Tank`) from a
+/// manifest, the same wiring as `sims::coffee_bench` but with parameters
+/// and endpoint names taken from the manifest.
+fn build_coffee(manifest: &Manifest) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let (pump_name, pump_spec) = find_model(manifest, "coffee::Pump");
+    let (controller_name, _) = find_model(manifest, "coffee::Controller");
+    let (tank_name, tank_spec) = find_model(manifest, "coffee::Tank");
+
+    check_connections(
+        manifest,
+        &[
+            ConnectionSpec {
+                from_model: controller_name.to_string(),
+                from_port: "pump_cmd".to_string(),
+                to_model: pump_name.to_string(),
+                to_port: "command".to_string(),
+            },
+            ConnectionSpec {
+                from_model: tank_name.to_string(),
+                from_port: "water_sense".to_string(),
+                to_model: controller_name.to_string(),
+                to_port: "water_sense".to_string(),
+            },
+            ConnectionSpec {
+                from_model: pump_name.to_string(),
+                from_port: "flow_rate".to_string(),
+                to_model: tank_name.to_string(),
+                to_port: "set_flow_rate".to_string(),
+            },
+        ],
+    );
+
+    let pump_flow_rate = pump_spec.param_f64("pump_flow_rate").unwrap_or(4.5e-6);
+    let init_tank_volume = tank_spec.param_f64("init_tank_volume").unwrap_or(1.5e-3);
+
+    let mut pump = coffee::Pump::new(pump_flow_rate);
+    let mut controller = coffee::Controller::new();
+    let mut tank = coffee::Tank::new(init_tank_volume);
+
+    let pump_mbox = Mailbox::new();
+    let controller_mbox = Mailbox::new();
+    let tank_mbox = Mailbox::new();
+
+    controller.pump_cmd.connect(coffee::Pump::command, &pump_mbox);
+    tank.water_sense
+        .connect(coffee::Controller::water_sense, &controller_mbox);
+    pump.flow_rate.connect(coffee::Tank::set_flow_rate, &tank_mbox);
+
+    let mut registry = EndpointRegistry::new();
+
+    if let Some(name) = endpoint_name(manifest, pump_name, "flow_rate") {
+        let flow_rate = EventSlot::new();
+        pump.flow_rate.connect_sink(&flow_rate);
+        registry.add_event_sink(flow_rate, name).unwrap();
+    }
+
+    let controller_addr = controller_mbox.address();
+    let tank_addr = tank_mbox.address();
+
+    if let Some(name) = endpoint_name(manifest, controller_name, "brew_cmd") {
+        let mut brew_cmd = EventSource::new();
+        brew_cmd.connect(coffee::Controller::brew_cmd, &controller_addr);
+        registry.add_event_source(brew_cmd, name).unwrap();
+    }
+    if let Some(name) = endpoint_name(manifest, controller_name, "brew_time") {
+        let mut brew_time = EventSource::new();
+        brew_time.connect(coffee::Controller::brew_time, &controller_addr);
+        registry.add_event_source(brew_time, name).unwrap();
+    }
+    if let Some(name) = endpoint_name(manifest, tank_name, "tank_fill") {
+        let mut tank_fill = EventSource::new();
+        tank_fill.connect(coffee::Tank::fill, &tank_addr);
+        registry.add_event_source(tank_fill, name).unwrap();
+    }
+
+    let sim = sims::sim_init(clock_config(manifest))
+        .add_model(controller, controller_mbox, controller_name)
+        .add_model(pump, pump_mbox, pump_name)
+        .add_model(tank, tank_mbox, tank_name)
+        .init(MonotonicTime::EPOCH)?
+        .0;
+
+    Ok((sim, registry))
+}
+
+/// The runtime config (`--threads`, `--clock-scale`) to assemble a manifest
+/// bench with, with its clock overridden by the manifest's own
+/// [`ClockSpec`] the same way `sims::coffee_bench`/`rt_coffee_bench`
+/// override it for their own fixed clock choice.
+fn clock_config(manifest: &Manifest) -> sims::RuntimeConfig {
+    clock_config_for(manifest, sims::runtime_config())
+}
+
+/// [`clock_config`], with the base config passed in instead of read from
+/// [`sims::runtime_config`], so the override logic can be tested on its own.
+fn clock_config_for(manifest: &Manifest, mut config: sims::RuntimeConfig) -> sims::RuntimeConfig {
+    config.clock = match manifest.clock {
+        ClockSpec::Fastest => sims::ClockMode::Fastest,
+        ClockSpec::WallClock if matches!(config.clock, sims::ClockMode::RealTime { .. }) => {
+            config.clock
+        }
+        ClockSpec::WallClock => sims::ClockMode::RealTime { scale: 1.0 },
+    };
+    config
+}
+
+/// Assemble the `bench_2` topology (a single `MyModel`) from a manifest.
+fn build_bench_2(manifest: &Manifest) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let (model_name, _) = find_model(manifest, "bench_2::MyModel");
+    check_connections(manifest, &[]);
+
+    let mut model = bench_2::MyModel::default();
+    let model_mbox = Mailbox::new();
+    let model_addr = model_mbox.address();
+
+    let mut registry = EndpointRegistry::new();
+
+    if let Some(name) = endpoint_name(manifest, model_name, "output") {
+        let output = EventBuffer::new();
+        model.output.connect_sink(&output);
+        registry.add_event_sink(output, name).unwrap();
+    }
+    if let Some(name) = endpoint_name(manifest, model_name, "input") {
+        let mut input = EventSource::new();
+        input.connect(bench_2::MyModel::my_input, &model_addr);
+        registry.add_event_source(input, name).unwrap();
+    }
+
+    let sim = sims::sim_init(clock_config(manifest))
+        .add_model(model, model_mbox, model_name)
+        .init(MonotonicTime::EPOCH)?
+        .0;
+
+    Ok((sim, registry))
+}
+
+/// Load a manifest from `path` and assemble the bench it describes.
+pub fn build_from_manifest(
+    path: impl AsRef<Path>,
+) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let contents = fs::read_to_string(path).expect("failed to read manifest file");
+    let manifest: Manifest = toml::from_str(&contents).expect("failed to parse manifest file");
+
+    build(&manifest)
+}
+
+/// Assemble a bench from an already-parsed [`Manifest`], by recognizing
+/// which of the built-in topologies its declared model types are.
+fn build(manifest: &Manifest) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let type_names: HashSet<&str> = manifest.models.values().map(|m| m.type_name.as_str()).collect();
+
+    const COFFEE_TYPES: [&str; 3] = ["coffee::Pump", "coffee::Controller", "coffee::Tank"];
+    const BENCH_2_TYPES: [&str; 1] = ["bench_2::MyModel"];
+
+    if type_names == COFFEE_TYPES.into_iter().collect() {
+        build_coffee(manifest)
+    } else if type_names == BENCH_2_TYPES.into_iter().collect() {
+        build_bench_2(manifest)
+    } else {
+        panic!(
+            "manifest model types {type_names:?} do not match a known topology \
+             ({COFFEE_TYPES:?} or {BENCH_2_TYPES:?})"
+        );
+    }
+}
+
+// Stashed the same way `sims::RUNTIME_CONFIG` is, and for the same reason:
+// `manifest_bench` below is passed to `server::run`/etc. by name, with no
+// way to also pass it this path.
+static MANIFEST_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Record the manifest path to build from. Must be called once, before the
+/// server is started with [`manifest_bench`] as its factory.
+pub fn set_manifest_path(path: impl Into<PathBuf>) {
+    MANIFEST_PATH
+        .set(path.into())
+        .expect("manifest path already set");
+}
+
+/// Bench factory that builds from the path recorded by
+/// [`set_manifest_path`].
+pub fn manifest_bench(_cfg: ()) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let path = MANIFEST_PATH
+        .get()
+        .expect("manifest path not set; call set_manifest_path before starting the server");
+
+    build_from_manifest(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(toml_params: &str) -> ModelSpec {
+        let params = toml_params
+            .parse::<toml::Value>()
+            .expect("invalid TOML in test fixture");
+        ModelSpec { type_name: String::new(), params: Some(params) }
+    }
+
+    #[test]
+    fn param_f64_reads_a_float() {
+        assert_eq!(spec("pump_flow_rate = 4.5e-6").param_f64("pump_flow_rate"), Some(4.5e-6));
+    }
+
+    #[test]
+    fn param_f64_coerces_an_integer() {
+        assert_eq!(spec("init_tank_volume = 2").param_f64("init_tank_volume"), Some(2.0));
+    }
+
+    #[test]
+    fn param_f64_is_none_when_absent() {
+        assert_eq!(
+            ModelSpec { type_name: String::new(), params: None }.param_f64("pump_flow_rate"),
+            None
+        );
+        assert_eq!(spec("other_key = 1.0").param_f64("pump_flow_rate"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a number")]
+    fn param_f64_panics_on_non_numeric_value() {
+        spec("pump_flow_rate = \"fast\"").param_f64("pump_flow_rate");
+    }
+
+    #[test]
+    fn clock_config_for_fastest_ignores_the_base_config() {
+        let manifest = Manifest {
+            models: HashMap::new(),
+            connections: Vec::new(),
+            endpoints: Vec::new(),
+            clock: ClockSpec::Fastest,
+        };
+        let base = sims::RuntimeConfig {
+            num_threads: Some(4),
+            clock: sims::ClockMode::RealTime { scale: 2.0 },
+        };
+
+        let config = clock_config_for(&manifest, base);
+        assert!(matches!(config.clock, sims::ClockMode::Fastest));
+        assert_eq!(config.num_threads, Some(4));
+    }
+
+    #[test]
+    fn clock_config_for_wall_clock_defaults_to_real_time_scale_1() {
+        let manifest = Manifest {
+            models: HashMap::new(),
+            connections: Vec::new(),
+            endpoints: Vec::new(),
+            clock: ClockSpec::WallClock,
+        };
+
+        let config = clock_config_for(&manifest, sims::RuntimeConfig::default());
+        assert!(matches!(config.clock, sims::ClockMode::RealTime { scale } if scale == 1.0));
+    }
+
+    #[test]
+    fn clock_config_for_wall_clock_keeps_an_already_scaled_clock() {
+        let manifest = Manifest {
+            models: HashMap::new(),
+            connections: Vec::new(),
+            endpoints: Vec::new(),
+            clock: ClockSpec::WallClock,
+        };
+        let base = sims::RuntimeConfig {
+            num_threads: None,
+            clock: sims::ClockMode::RealTime { scale: 10.0 },
+        };
+
+        let config = clock_config_for(&manifest, base);
+        assert!(matches!(config.clock, sims::ClockMode::RealTime { scale } if scale == 10.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "do not match a known topology")]
+    fn build_panics_on_an_unrecognized_topology() {
+        let manifest = Manifest {
+            models: HashMap::from([(
+                "mystery".to_string(),
+                ModelSpec { type_name: "mystery::Thing".to_string(), params: None },
+            )]),
+            connections: Vec::new(),
+            endpoints: Vec::new(),
+            clock: ClockSpec::Fastest,
+        };
+
+        let _ = build(&manifest);
+    }
+}
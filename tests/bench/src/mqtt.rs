@@ -0,0 +1,199 @@
+//! MQTT bridge for a bench's [`EndpointRegistry`], for hardware-/
+//! software-in-the-loop setups: every registered sink publishes its
+//! events to a topic, and every registered source is driven by whatever
+//! is published to its topic. `run_with_mqtt`/`run_local_with_mqtt`/
+//! `run_vsock_with_mqtt` start the bridge alongside the gRPC server.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use nexosim::registry::EndpointRegistry;
+use nexosim::simulation::{Simulation, SimulationError};
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use tokio_vsock::{VsockAddr, VsockListener};
+
+use crate::server_ext::ServeError;
+
+/// Errors bridging a bench's registry to an MQTT broker.
+#[derive(Debug, thiserror::Error)]
+pub enum BridgeError {
+    #[error("invalid MQTT broker url `{0}`")]
+    InvalidBrokerUrl(String),
+    #[error("MQTT client error: {0}")]
+    Client(#[from] rumqttc::ClientError),
+    #[error("MQTT connection error: {0}")]
+    Connection(#[from] rumqttc::ConnectionError),
+}
+
+/// The MQTT topic a bench's endpoint named `endpoint_name` is bridged to.
+pub fn topic(bench_name: &str, endpoint_name: &str) -> String {
+    format!("nexosim/{bench_name}/{endpoint_name}")
+}
+
+/// Bridge `registry`'s endpoints to `broker_url` (e.g.
+/// `mqtt://localhost:1883`), publishing/subscribing under
+/// `nexosim/<bench_name>/<endpoint_name>`. Runs until the connection to the
+/// broker fails.
+pub async fn run_bridge(
+    bench_name: &str,
+    registry: EndpointRegistry,
+    broker_url: &str,
+) -> Result<(), BridgeError> {
+    let url = url::Url::parse(broker_url)
+        .map_err(|_| BridgeError::InvalidBrokerUrl(broker_url.to_string()))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| BridgeError::InvalidBrokerUrl(broker_url.to_string()))?;
+    let port = url.port().unwrap_or(1883);
+
+    let mut mqtt_options = MqttOptions::new(format!("nexosim-{bench_name}"), host, port);
+    mqtt_options.set_keep_alive(Duration::from_secs(30));
+
+    let (client, mut event_loop) = AsyncClient::new(mqtt_options, 64);
+
+    let source_names = registry.event_source_names();
+    for name in &source_names {
+        client
+            .subscribe(topic(bench_name, name), QoS::AtLeastOnce)
+            .await?;
+    }
+
+    let publish_client = client.clone();
+    let sink_names = registry.event_sink_names();
+    let publish_registry = registry.clone();
+    let publish_bench_name = bench_name.to_string();
+    let publish_task = tokio::spawn(async move {
+        loop {
+            for name in &sink_names {
+                let payloads = publish_registry.drain_event_sink_json(name).unwrap_or_default();
+                for payload in payloads {
+                    if let Err(err) = publish_client
+                        .publish(topic(&publish_bench_name, name), QoS::AtLeastOnce, false, payload)
+                        .await
+                    {
+                        eprintln!("mqtt publish on `{name}` dropped: {err}");
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+
+    let topic_prefix = topic(bench_name, "");
+    let result = loop {
+        let notification = match event_loop.poll().await {
+            Ok(notification) => notification,
+            Err(err) => break Err(err.into()),
+        };
+        if let Event::Incoming(Packet::Publish(publish)) = notification {
+            if let Some(name) = publish.topic.strip_prefix(&topic_prefix) {
+                if let Err(err) = registry.send_event_json(name, &publish.payload) {
+                    eprintln!("mqtt event on `{name}` not delivered: {err}");
+                }
+            }
+        }
+    };
+
+    publish_task.abort();
+    result
+}
+
+/// Spawn the MQTT bridge for `registry` against `broker_url`, logging (and
+/// giving up on) a failed bridge rather than taking the gRPC server down
+/// with it.
+fn spawn_bridge(bench_name: String, registry: EndpointRegistry, broker_url: String) {
+    tokio::spawn(async move {
+        if let Err(err) = run_bridge(&bench_name, registry, &broker_url).await {
+            eprintln!("mqtt bridge for `{bench_name}` stopped: {err}");
+        }
+    });
+}
+
+/// Serve `factory`'s bench over HTTP at `addr`, bridged to `broker_url`.
+pub fn run_with_mqtt<F, Args>(
+    factory: F,
+    addr: SocketAddr,
+    bench_name: &str,
+    broker_url: &str,
+) -> Result<(), ServeError>
+where
+    F: Fn(Args) -> Result<(Simulation, EndpointRegistry), SimulationError>,
+    Args: Default,
+{
+    let (simulation, registry) = factory(Args::default())?;
+    let bridge_registry = registry.clone();
+    let bench_name = bench_name.to_string();
+    let broker_url = broker_url.to_string();
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async move {
+        spawn_bridge(bench_name, bridge_registry, broker_url);
+
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        nexosim::server::grpc_router(simulation, registry)
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+            .await
+            .map_err(ServeError::from)
+    })
+}
+
+/// Serve `factory`'s bench over the local Unix socket at `addr`, bridged
+/// to `broker_url`.
+pub fn run_local_with_mqtt<F, Args>(
+    factory: F,
+    addr: String,
+    bench_name: &str,
+    broker_url: &str,
+) -> Result<(), ServeError>
+where
+    F: Fn(Args) -> Result<(Simulation, EndpointRegistry), SimulationError>,
+    Args: Default,
+{
+    let (simulation, registry) = factory(Args::default())?;
+    let bridge_registry = registry.clone();
+    let bench_name = bench_name.to_string();
+    let broker_url = broker_url.to_string();
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async move {
+        spawn_bridge(bench_name, bridge_registry, broker_url);
+
+        let _ = std::fs::remove_file(&addr);
+        let listener = tokio::net::UnixListener::bind(&addr)?;
+        nexosim::server::grpc_router(simulation, registry)
+            .serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener))
+            .await
+            .map_err(ServeError::from)
+    })
+}
+
+/// Serve `factory`'s bench over a VM-socket listening on `cid:port`,
+/// bridged to `broker_url`.
+pub fn run_vsock_with_mqtt<F, Args>(
+    factory: F,
+    cid: u32,
+    port: u32,
+    bench_name: &str,
+    broker_url: &str,
+) -> Result<(), ServeError>
+where
+    F: Fn(Args) -> Result<(Simulation, EndpointRegistry), SimulationError>,
+    Args: Default,
+{
+    let (simulation, registry) = factory(Args::default())?;
+    let bridge_registry = registry.clone();
+    let bench_name = bench_name.to_string();
+    let broker_url = broker_url.to_string();
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async move {
+        spawn_bridge(bench_name, bridge_registry, broker_url);
+
+        let listener = VsockListener::bind(VsockAddr::new(cid, port))?;
+        nexosim::server::grpc_router(simulation, registry)
+            .serve_with_incoming(listener.incoming())
+            .await
+            .map_err(ServeError::from)
+    })
+}
@@ -0,0 +1,21 @@
+//! Shared error type for [`crate::vsock`]/[`crate::mqtt`] and
+//! `server::run`/`run_local`, so `main`'s per-transport `match` blocks can
+//! unify on one `Result` type.
+#[derive(Debug, thiserror::Error)]
+pub enum ServeError {
+    #[error("failed to build the bench: {0}")]
+    Simulation(#[from] nexosim::simulation::SimulationError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("{0}")]
+    External(String),
+}
+
+/// Convert the opaque error `server::run`/`run_local` return into a
+/// [`ServeError`], so it can share a `Result` type with the entry points
+/// above.
+pub fn external<E: std::fmt::Debug>(err: E) -> ServeError {
+    ServeError::External(format!("{err:?}"))
+}
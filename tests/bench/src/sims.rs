@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 use nexosim::ports::{EventSource, EventBuffer, EventSlot};
 use nexosim::registry::EndpointRegistry;
 use nexosim::simulation::{Mailbox, SimInit, Simulation, SimulationError};
@@ -6,64 +8,73 @@ use nexosim::time::{MonotonicTime, AutoSystemClock};
 use crate::coffee;
 use crate::bench_2;
 
+/// How a bench's simulated time is driven.
+#[derive(Debug, Clone, Copy)]
+pub enum ClockMode {
+    /// Run as fast as the executor allows, with no relation to wall-clock
+    /// time.
+    Fastest,
+    /// Tie simulated time to wall-clock time, advancing at `scale` times
+    /// wall-clock speed (e.g. `10.0` for 10x faster, `0.5` for half speed).
+    RealTime { scale: f64 },
+}
 
-/// Create the bench assembly.
-pub fn coffee_bench(
-    init_tank_volume: Option<f64>,
-) -> Result<(Simulation, EndpointRegistry), SimulationError> {
-
-    let pump_flow_rate = 4.5e-6;
-    let init_tank_volume = init_tank_volume.unwrap_or(1.5e-3);
-
-    let mut pump = coffee::Pump::new(pump_flow_rate);
-    let mut controller = coffee::Controller::new();
-    let mut tank = coffee::Tank::new(init_tank_volume);
-
-    // Mailboxes.
-    let pump_mbox = Mailbox::new();
-    let controller_mbox = Mailbox::new();
-    let tank_mbox = Mailbox::new();
+/// Runtime configuration shared by every bench: how many executor threads
+/// to use, and how simulated time advances.
+#[derive(Debug, Clone, Copy)]
+pub struct RuntimeConfig {
+    /// Number of executor threads. `None` leaves it to `SimInit`'s default.
+    pub num_threads: Option<usize>,
+    pub clock: ClockMode,
+}
 
-    // Connections.
-    controller.pump_cmd.connect(coffee::Pump::command, &pump_mbox);
-    tank.water_sense
-        .connect(coffee::Controller::water_sense, &controller_mbox);
-    pump.flow_rate.connect(coffee::Tank::set_flow_rate, &tank_mbox);
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self { num_threads: None, clock: ClockMode::Fastest }
+    }
+}
 
-    // Endpoints.
-    let mut registry = EndpointRegistry::new();
+// `server::run`/etc. build a bench factory's `Args` with `Default::default()`,
+// so CLI-provided runtime config is stashed here instead and picked up by
+// the bench factories below (`manifest::MANIFEST_PATH` does the same).
+static RUNTIME_CONFIG: OnceLock<RuntimeConfig> = OnceLock::new();
 
-    let flow_rate = EventSlot::new();
-    pump.flow_rate.connect_sink(&flow_rate);
-    registry.add_event_sink(flow_rate, "flow_rate").unwrap();
+/// Record the runtime config benches should use. Must be called once,
+/// before the server is started.
+pub fn set_runtime_config(config: RuntimeConfig) {
+    RUNTIME_CONFIG.set(config).expect("runtime config already set");
+}
 
-    let controller_addr = controller_mbox.address();
-    let tank_addr = tank_mbox.address();
+pub(crate) fn runtime_config() -> RuntimeConfig {
+    RUNTIME_CONFIG.get().copied().unwrap_or_default()
+}
 
-    let mut brew_cmd = EventSource::new();
-    brew_cmd.connect(coffee::Controller::brew_cmd, &controller_addr);
-    let mut brew_time = EventSource::new();
-    brew_time.connect(coffee::Controller::brew_time, &controller_addr);
-    let mut tank_fill = EventSource::new();
-    tank_fill.connect(coffee::Tank::fill, &tank_addr);
-    registry.add_event_source(brew_cmd, "brew_cmd").unwrap();
-    registry.add_event_source(brew_time, "brew_time").unwrap();
-    registry.add_event_source(tank_fill, "tank_fill").unwrap();
+/// The `ClockMode` a `--clock-scale` value should select: `RealTime` at that
+/// scale if given, `Fastest` (the default) otherwise.
+pub fn clock_mode_for_scale(clock_scale: Option<f64>) -> ClockMode {
+    match clock_scale {
+        Some(scale) => ClockMode::RealTime { scale },
+        None => ClockMode::Fastest,
+    }
+}
 
-    // Assembly and initialization.
-    let sim = SimInit::new()
-        .add_model(controller, controller_mbox, "controller")
-        .add_model(pump, pump_mbox, "pump")
-        .add_model(tank, tank_mbox, "tank")
-        .init(MonotonicTime::EPOCH)?
-        .0;
+pub(crate) fn sim_init(config: RuntimeConfig) -> SimInit {
+    let sim_init = match config.num_threads {
+        Some(num_threads) => SimInit::with_num_threads(num_threads),
+        None => SimInit::new(),
+    };
 
-    Ok((sim, registry))
+    match config.clock {
+        ClockMode::Fastest => sim_init,
+        ClockMode::RealTime { scale } => sim_init.set_clock(AutoSystemClock::with_scale(scale)),
+    }
 }
 
-/// Create the bench assembly.
-pub fn rt_coffee_bench(
+/// Create the bench assembly with `config`'s executor threads and clock (the
+/// `RealTime` scale only if `config.clock` asks for it).
+fn build_coffee_bench(
     init_tank_volume: Option<f64>,
+    config: RuntimeConfig,
 ) -> Result<(Simulation, EndpointRegistry), SimulationError> {
 
     let pump_flow_rate = 4.5e-6;
@@ -105,17 +116,37 @@ pub fn rt_coffee_bench(
     registry.add_event_source(tank_fill, "tank_fill").unwrap();
 
     // Assembly and initialization.
-    let sim = SimInit::new()
+    let sim = sim_init(config)
         .add_model(controller, controller_mbox, "controller")
         .add_model(pump, pump_mbox, "pump")
         .add_model(tank, tank_mbox, "tank")
-        .set_clock(AutoSystemClock::new())
         .init(MonotonicTime::EPOCH)?
         .0;
 
     Ok((sim, registry))
 }
 
+/// Create the bench assembly, run as fast as the executor allows.
+pub fn coffee_bench(
+    init_tank_volume: Option<f64>,
+) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let mut config = runtime_config();
+    config.clock = ClockMode::Fastest;
+    build_coffee_bench(init_tank_volume, config)
+}
+
+/// Create the bench assembly, with simulated time tied to wall-clock time
+/// (scaled, if the runtime config asks for it).
+pub fn rt_coffee_bench(
+    init_tank_volume: Option<f64>,
+) -> Result<(Simulation, EndpointRegistry), SimulationError> {
+    let mut config = runtime_config();
+    if !matches!(config.clock, ClockMode::RealTime { .. }) {
+        config.clock = ClockMode::RealTime { scale: 1.0 };
+    }
+    build_coffee_bench(init_tank_volume, config)
+}
+
 
 pub fn bench_2(_cfg: bench_2::TestLoad) -> Result<(Simulation, EndpointRegistry), SimulationError> {
     let mut model = bench_2::MyModel::default();
@@ -136,10 +167,27 @@ pub fn bench_2(_cfg: bench_2::TestLoad) -> Result<(Simulation, EndpointRegistry)
     registry.add_event_source(input, "input").unwrap();
 
     // Assembly and initialization.
-    let sim = SimInit::new()
+    let sim = sim_init(runtime_config())
         .add_model(model, model_mbox, "model")
         .init(MonotonicTime::EPOCH)?
         .0;
 
     Ok((sim, registry))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_mode_for_scale_none_is_fastest() {
+        assert!(matches!(clock_mode_for_scale(None), ClockMode::Fastest));
+    }
+
+    #[test]
+    fn clock_mode_for_scale_some_is_real_time_at_that_scale() {
+        assert!(
+            matches!(clock_mode_for_scale(Some(2.5)), ClockMode::RealTime { scale } if scale == 2.5)
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! VM-socket (AF_VSOCK) transport for the nexosim gRPC server, so a bench
+//! running inside a guest VM can be driven by a host-side client without
+//! TCP.
+
+use nexosim::registry::EndpointRegistry;
+use nexosim::simulation::{Simulation, SimulationError};
+
+use tokio_vsock::{VsockAddr, VsockListener};
+
+use crate::server_ext::ServeError;
+
+/// Serve `factory`'s bench over a VM-socket listening on `cid:port`.
+pub fn run_vsock<F, Args>(factory: F, cid: u32, port: u32) -> Result<(), ServeError>
+where
+    F: Fn(Args) -> Result<(Simulation, EndpointRegistry), SimulationError>,
+    Args: Default,
+{
+    let (simulation, registry) = factory(Args::default())?;
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async move {
+        let listener = VsockListener::bind(VsockAddr::new(cid, port))?;
+        nexosim::server::grpc_router(simulation, registry)
+            .serve_with_incoming(listener.incoming())
+            .await
+            .map_err(ServeError::from)
+    })
+}